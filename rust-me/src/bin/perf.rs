@@ -1,6 +1,11 @@
 use clap::Parser;
-use rust_me::Queue;
-use std::time::Instant; // Import clap
+use rust_me::queue::Order;
+use rust_me::{Queue, StatsdSink};
+use std::time::{Duration, Instant};
+
+/// Orders pulled per `dequeue_batch` call, amortizing the per-order
+/// atomic/bounds-check overhead `dequeue()` pays one at a time.
+const BATCH_SIZE: usize = 256;
 
 /// HFT performance benchmark consumer
 #[derive(Parser, Debug)]
@@ -13,13 +18,25 @@ struct Args {
     /// Path to the queue file
     #[arg(long, default_value = "/tmp/sex")]
     queue: String,
+
+    /// StatsD collector to report `orders.dequeued`/`orders.empty_spins`/
+    /// `queue.depth` to (e.g. `127.0.0.1:8125`). Defaults to no metrics.
+    #[arg(long)]
+    statsd: Option<String>,
 }
 
 fn main() -> Result<(), Box<dyn std::error::Error>> {
     let args = Args::parse(); // Parse arguments
 
     println!("[PERF] Initializing queue: {}", args.queue);
-    let mut queue = Queue::open(&args.queue)?; // Use arg for path
+    let mut queue = match &args.statsd {
+        Some(target) => {
+            println!("[PERF] Reporting metrics to statsd at {}", target);
+            let sink = StatsdSink::new(target, Duration::from_secs(1))?;
+            Queue::open_with_metrics(&args.queue, sink, Duration::from_secs(1))?
+        }
+        None => Queue::open(&args.queue)?,
+    };
 
     println!(
         "[PERF] Rust consumer: consuming {} orders...\n",
@@ -28,19 +45,22 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
 
     let start = Instant::now();
     let mut count = 0u64;
+    let mut batch = vec![Order::new(0, 0, [0; 8], 0, 0, 0); BATCH_SIZE];
 
     loop {
-        match queue.dequeue() {
-            Ok(Some(_order)) => {
-                count += 1;
-                if count == args.orders {
-                    break; // We're done, break the (only) loop
-                }
-            }
-            Ok(None) => {
+        // Never pull more than the orders still wanted.
+        let want = std::cmp::min(BATCH_SIZE as u64, args.orders - count) as usize;
+        match queue.dequeue_batch(&mut batch[..want]) {
+            Ok(0) => {
                 // Queue is empty, spin and try again
                 std::hint::spin_loop();
             }
+            Ok(n) => {
+                count += n as u64;
+                if count >= args.orders {
+                    break; // We're done, break the (only) loop
+                }
+            }
             Err(e) => {
                 println!("Queue error: {}", e);
                 break; // Error, exit
@@ -1,7 +1,7 @@
 use clap::{Parser, Subcommand};
 use env_logger::Env;
 use log::{debug, error, info, warn};
-use rust_me::{Order, Queue};
+use rust_me::{spawn_local_broker, Order, OrderTransport, Queue};
 use std::thread;
 use std::time::{Duration, Instant};
 
@@ -46,6 +46,14 @@ enum Commands {
 
     /// Run integration test (requires Go OMS running)
     Integration,
+
+    /// Run the integration test against an in-process queue instead of a
+    /// real Go OMS, so it works the same in CI with nothing else running
+    #[command(name = "self-test")]
+    SelfTest {
+        #[arg(short, long, default_value_t = 50000)]
+        count: u64,
+    },
 }
 
 fn main() {
@@ -64,6 +72,7 @@ fn main() {
         } => stream_orders(duration_secs, spin),
         Commands::Monitor { max_depth } => monitor_queue(max_depth),
         Commands::Integration => integration_test(),
+        Commands::SelfTest { count } => self_test(count),
     }
 }
 
@@ -270,7 +279,7 @@ fn stream_orders(duration_secs: u64, use_spin: bool) {
         }
 
         // Report every second
-        if dequeued % 10000 == 0 && dequeued > 0 {
+        if dequeued.is_multiple_of(10000) && dequeued > 0 {
             let elapsed = start.elapsed().as_secs_f64();
             let throughput = dequeued as f64 / elapsed;
             println!(
@@ -406,7 +415,7 @@ fn integration_test() {
                     stats.record_error("invalid_quantity");
                 }
 
-                if stats.dequeued % 5000 == 0 {
+                if stats.dequeued.is_multiple_of(5000) {
                     let throughput = stats.dequeued as f64 / start.elapsed().as_secs_f64();
                     println!(
                         "[{:6.1}s] Dequeued: {}, Throughput: {:.0}/sec, Depth: {}",
@@ -440,6 +449,57 @@ fn integration_test() {
     }
 }
 
+/// Like `integration_test`, but against a `MemoryQueue` fed by a local
+/// producer thread instead of a real `/tmp/sex` file, so it passes
+/// deterministically with no Go OMS involved.
+fn self_test(count: u64) {
+    info!("Starting self-test against an in-process queue...");
+    println!("\n=== Self-Test (in-process) ===\n");
+
+    let orders: Vec<Order> = (0..count)
+        .map(|i| Order::new(i + 1, (i % 8) as u32, *b"SELFTEST", 1, 100, (i % 2) as u8))
+        .collect();
+    let (producer, mut queue) = spawn_local_broker(4096, orders);
+
+    let start = Instant::now();
+    let mut stats = TestStats::new();
+
+    while stats.dequeued < count {
+        match queue.dequeue_spin(1000) {
+            Ok(Some(order)) => {
+                stats.record_success(&order);
+                if stats.dequeued.is_multiple_of(5000) {
+                    let throughput = stats.dequeued as f64 / start.elapsed().as_secs_f64();
+                    println!(
+                        "[{:6.1}s] Dequeued: {}, Throughput: {:.0}/sec, Depth: {}",
+                        start.elapsed().as_secs_f64(),
+                        stats.dequeued,
+                        throughput,
+                        queue.depth()
+                    );
+                }
+            }
+            Ok(None) => stats.empty_checks += 1,
+            Err(e) => {
+                error!("Dequeue error: {}", e);
+                stats.record_error(&e.to_string());
+            }
+        }
+    }
+    producer.join().expect("producer thread panicked");
+
+    println!("\n=== Self-Test Results ===\n");
+    stats.print_summary(start.elapsed());
+
+    if stats.errors == 0 && stats.dequeued == count {
+        println!("\n✓ Self-test PASSED");
+        info!("Self-test passed");
+    } else {
+        println!("\n✗ Self-test FAILED");
+        error!("Self-test failed");
+    }
+}
+
 /// Helper struct for test statistics
 struct TestStats {
     dequeued: u64,
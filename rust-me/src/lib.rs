@@ -0,0 +1,18 @@
+//! Rust-side consumer for the shared-memory order queue written by the Go OMS.
+//!
+//! The queue lives in `queue`, with an in-process backend (`memory`), a
+//! common trait over both (`transport`), metrics sinks (`metrics`) and an
+//! async `Stream` adapter (`stream`) beside it. The matching engine, perf
+//! harnesses and test harness are binaries that link against this library.
+
+pub mod memory;
+pub mod metrics;
+pub mod queue;
+pub mod stream;
+pub mod transport;
+
+pub use memory::{spawn_local_broker, MemoryQueue};
+pub use metrics::{Metrics, NoopMetrics, StatsdSink};
+pub use queue::{Order, Queue, QueueBuilder, QueueError};
+pub use stream::{QueueStream, QueueStreamBuilder};
+pub use transport::OrderTransport;
@@ -1,12 +1,26 @@
-use rust_me::queue::{Order, Queue, QueueError};
+use rust_me::queue::{Order, PoisonPolicy, Queue, QueueError};
 use std::time::Instant;
 
+/// Within any 1000 consecutive orders, more than 50 dead-lettered ones
+/// means the feed itself is corrupt rather than just carrying the odd bad
+/// order, and the engine should stop rather than keep dead-lettering.
+const DLQ_POISON_POLICY: PoisonPolicy = PoisonPolicy {
+    window: 1000,
+    max_poisoned: 50,
+};
+
 fn main() -> Result<(), Box<dyn std::error::Error>> {
     println!("[Engine] Starting Rust matching engine (rustc 1.91.0)...");
 
-    // Open order queue created by Go OMS
-    let mut order_queue = Queue::open("/tmp/sex")?;
-    println!("[Engine] Connected to order queue");
+    // Open order queue created by Go OMS. Orders failing `is_well_formed`
+    // are dead-lettered to /tmp/sex_dlq and never reach `execute_order`.
+    let mut order_queue = Queue::open_with_dlq(
+        "/tmp/sex",
+        "/tmp/sex_dlq",
+        is_well_formed,
+        DLQ_POISON_POLICY,
+    )?;
+    println!("[Engine] Connected to order queue (DLQ: /tmp/sex_dlq)");
 
     // Open status feedback queue
     let mut status_queue = Queue::open("/tmp/sex_status")?;
@@ -19,8 +33,16 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
 
     loop {
         // Try to dequeue with spinning for lower latency
-        match order_queue.dequeue_spin(100)? {
-            Some(order) => {
+        match order_queue.dequeue_spin(100) {
+            Err(QueueError::PoisonThresholdExceeded { poisoned, window }) => {
+                eprintln!(
+                    "[Engine] {} of the last {} orders were dead-lettered, stopping",
+                    poisoned, window
+                );
+                return Err(QueueError::PoisonThresholdExceeded { poisoned, window }.into());
+            }
+            Err(e) => return Err(e.into()),
+            Ok(Some(order)) => {
                 order_count += 1;
 
                 // Execute trade (simplified)
@@ -39,7 +61,7 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
                 }
 
                 // Report throughput every 1000 orders
-                if order_count % 1000 == 0 {
+                if order_count.is_multiple_of(1000) {
                     let elapsed = start.elapsed().as_secs_f64();
                     let throughput = order_count as f64 / elapsed;
                     println!(
@@ -51,7 +73,7 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
                     );
                 }
             }
-            None => {
+            Ok(None) => {
                 // Queue empty after spinning
                 std::thread::yield_now();
             }
@@ -59,14 +81,16 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
     }
 }
 
+/// Whether an order is well-formed enough to execute at all. Orders
+/// failing this go to the DLQ instead of `execute_order`, which does not
+/// check quantity or price itself.
+fn is_well_formed(order: &Order) -> bool {
+    order.quantity != 0 && order.price != 0
+}
+
 /// Simulate order execution (matching engine logic goes here)
 fn execute_order(order: &Order) -> bool {
-    // Validate order
-    if order.quantity == 0 || order.price == 0 {
-        return false; // Reject invalid
-    }
-
     // In production: check order book, execute match, update positions
     // For now: accept 90% of orders
-    order.order_id % 10 != 0
+    !order.order_id.is_multiple_of(10)
 }
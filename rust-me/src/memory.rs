@@ -0,0 +1,121 @@
+//! A pure-Rust, in-process [`OrderTransport`] backend, so integration-style
+//! tests can exercise the consumer paths (`stream`, `batch`, ...) against a
+//! deterministic queue instead of needing a running Go OMS and a real
+//! `/tmp/sex` file.
+
+use crate::queue::{Order, QueueError};
+use crate::transport::OrderTransport;
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+/// An in-process bounded order queue behind a `Mutex<VecDeque<_>>`. Cheap to
+/// `clone` — clones share the same underlying buffer via `Arc`, so a
+/// producer and a consumer can each hold their own handle to the same
+/// queue, the same way a producer and consumer process each hold their own
+/// mapping of the same shared-memory file.
+#[derive(Clone)]
+pub struct MemoryQueue {
+    inner: Arc<Mutex<VecDeque<Order>>>,
+    capacity: u64,
+}
+
+impl MemoryQueue {
+    pub fn new(capacity: u64) -> Self {
+        MemoryQueue {
+            inner: Arc::new(Mutex::new(VecDeque::with_capacity(capacity as usize))),
+            capacity,
+        }
+    }
+}
+
+impl OrderTransport for MemoryQueue {
+    fn enqueue(&mut self, order: Order) -> Result<(), QueueError> {
+        let mut inner = self.inner.lock().unwrap();
+        if inner.len() as u64 >= self.capacity {
+            return Err(QueueError::QueueFull {
+                depth: inner.len() as u64,
+            });
+        }
+        inner.push_back(order);
+        Ok(())
+    }
+
+    fn dequeue(&mut self) -> Result<Option<Order>, QueueError> {
+        Ok(self.inner.lock().unwrap().pop_front())
+    }
+
+    fn dequeue_spin(&mut self, spins: u32) -> Result<Option<Order>, QueueError> {
+        for _ in 0..spins {
+            if let Some(order) = self.inner.lock().unwrap().pop_front() {
+                return Ok(Some(order));
+            }
+            std::hint::spin_loop();
+        }
+        Ok(None)
+    }
+
+    fn depth(&self) -> u64 {
+        self.inner.lock().unwrap().len() as u64
+    }
+
+    fn capacity(&self) -> u64 {
+        self.capacity
+    }
+}
+
+/// Spawn a producer thread that enqueues `orders` onto a fresh
+/// `MemoryQueue` (backing off with `thread::yield_now` whenever it's full),
+/// and return a consumer-side handle to that same queue alongside the
+/// producer's `JoinHandle`. A local-broker-style stand-in for wiring a real
+/// producer and consumer process together, but entirely in-process and
+/// without touching the filesystem.
+pub fn spawn_local_broker(
+    capacity: u64,
+    orders: Vec<Order>,
+) -> (thread::JoinHandle<()>, MemoryQueue) {
+    let queue = MemoryQueue::new(capacity);
+    let mut producer = queue.clone();
+
+    let handle = thread::spawn(move || {
+        for order in orders {
+            loop {
+                match producer.enqueue(order) {
+                    Ok(()) => break,
+                    Err(QueueError::QueueFull { .. }) => thread::yield_now(),
+                    Err(_) => break,
+                }
+            }
+        }
+    });
+
+    (handle, queue)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn local_broker_delivers_every_order_in_order() {
+        const COUNT: u64 = 5000;
+
+        let orders: Vec<Order> = (0..COUNT)
+            .map(|i| Order::new(i + 1, 0, *b"TESTSYMB", 1, 100, 0))
+            .collect();
+
+        let (handle, mut consumer) = spawn_local_broker(64, orders);
+
+        let mut received = Vec::with_capacity(COUNT as usize);
+        while received.len() < COUNT as usize {
+            match consumer.dequeue_spin(1000) {
+                Ok(Some(order)) => received.push(order.order_id),
+                Ok(None) => continue,
+                Err(e) => panic!("unexpected dequeue error: {}", e),
+            }
+        }
+
+        handle.join().unwrap();
+        assert_eq!(received, (1..=COUNT).collect::<Vec<_>>());
+    }
+}
@@ -0,0 +1,258 @@
+//! Pluggable metrics sinks, so throughput/backpressure numbers can go to a
+//! real collector instead of the `println!`s every binary in this crate
+//! currently recomputes by hand.
+//!
+//! [`Queue`](crate::queue::Queue) reports through a `Metrics` trait object;
+//! [`NoopMetrics`] is the default (zero-config, zero-overhead-ish) sink, and
+//! [`StatsdSink`] ships counters/gauges/timings to a StatsD collector over
+//! UDP, buffered and flushed from a background thread so the hot path never
+//! pays a syscall per order.
+
+use std::io;
+use std::net::UdpSocket;
+use std::sync::{Arc, Mutex, Weak};
+use std::thread;
+use std::time::Duration;
+
+/// A sink for counters, gauges, and timings emitted by the queue.
+pub trait Metrics: Send + Sync {
+    /// Increment a monotonic counter by `value`.
+    fn counter(&self, name: &str, value: u64);
+    /// Report the current value of a point-in-time gauge.
+    fn gauge(&self, name: &str, value: i64);
+    /// Report how long an operation took.
+    fn timing(&self, name: &str, duration: Duration);
+}
+
+/// Discards everything. The default sink when no collector is configured.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct NoopMetrics;
+
+impl Metrics for NoopMetrics {
+    fn counter(&self, _name: &str, _value: u64) {}
+    fn gauge(&self, _name: &str, _value: i64) {}
+    fn timing(&self, _name: &str, _duration: Duration) {}
+}
+
+/// Buffer metrics are held in before being flushed over UDP, either because
+/// the flush interval elapsed or because the buffer filled up.
+const MAX_BUFFERED_METRICS: usize = 500;
+
+/// A StatsD sink: formats metrics as `name:value|type` lines, batches them,
+/// and flushes a batch per UDP datagram on a background thread every
+/// `flush_interval` (or immediately if `MAX_BUFFERED_METRICS` is reached).
+pub struct StatsdSink {
+    socket: UdpSocket,
+    buffer: Mutex<Vec<String>>,
+}
+
+impl StatsdSink {
+    /// Connect to a StatsD collector at `target` (e.g. `"127.0.0.1:8125"`)
+    /// and start the background flush thread. The thread holds only a
+    /// `Weak` reference, so it exits on its own once the last `Arc` to the
+    /// sink is dropped rather than needing an explicit shutdown call.
+    pub fn new(target: &str, flush_interval: Duration) -> io::Result<Arc<StatsdSink>> {
+        let socket = UdpSocket::bind("0.0.0.0:0")?;
+        socket.connect(target)?;
+
+        let sink = Arc::new(StatsdSink {
+            socket,
+            buffer: Mutex::new(Vec::new()),
+        });
+
+        let weak_sink: Weak<StatsdSink> = Arc::downgrade(&sink);
+        thread::spawn(move || loop {
+            thread::sleep(flush_interval);
+            match weak_sink.upgrade() {
+                Some(sink) => sink.flush(),
+                None => return,
+            }
+        });
+
+        Ok(sink)
+    }
+
+    fn push(&self, line: String) {
+        let mut buffer = self.buffer.lock().unwrap();
+        buffer.push(line);
+        if buffer.len() >= MAX_BUFFERED_METRICS {
+            let batch = std::mem::take(&mut *buffer);
+            drop(buffer);
+            self.send(batch);
+        }
+    }
+
+    /// Flush any buffered metrics now, in one UDP datagram per batch.
+    pub fn flush(&self) {
+        let batch = std::mem::take(&mut *self.buffer.lock().unwrap());
+        self.send(batch);
+    }
+
+    fn send(&self, batch: Vec<String>) {
+        if batch.is_empty() {
+            return;
+        }
+        let payload = batch.join("\n");
+        // Best-effort: a dropped metrics datagram shouldn't take down the
+        // matching engine.
+        let _ = self.socket.send(payload.as_bytes());
+    }
+}
+
+impl Metrics for StatsdSink {
+    fn counter(&self, name: &str, value: u64) {
+        self.push(format!("{}:{}|c", name, value));
+    }
+
+    fn gauge(&self, name: &str, value: i64) {
+        self.push(format!("{}:{}|g", name, value));
+    }
+
+    fn timing(&self, name: &str, duration: Duration) {
+        self.push(format!("{}:{}|ms", name, duration.as_millis()));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::queue::{create_spsc_for_test, Order, Queue, QUEUE_CAPACITY};
+    use std::net::UdpSocket;
+    use std::sync::atomic::{AtomicU32, Ordering};
+
+    /// An in-memory spy standing in for a real collector, so tests can
+    /// assert on exactly which counters `Queue` reported without a network
+    /// round trip.
+    #[derive(Default)]
+    struct RecordingMetrics {
+        counters: Mutex<Vec<(String, u64)>>,
+    }
+
+    impl RecordingMetrics {
+        fn total(&self, name: &str) -> u64 {
+            self.counters
+                .lock()
+                .unwrap()
+                .iter()
+                .filter(|(n, _)| n == name)
+                .map(|(_, v)| v)
+                .sum()
+        }
+    }
+
+    impl Metrics for RecordingMetrics {
+        fn counter(&self, name: &str, value: u64) {
+            self.counters.lock().unwrap().push((name.to_string(), value));
+        }
+        fn gauge(&self, _name: &str, _value: i64) {}
+        fn timing(&self, _name: &str, _duration: Duration) {}
+    }
+
+    static TEST_COUNTER: AtomicU32 = AtomicU32::new(0);
+
+    fn temp_queue_path() -> std::path::PathBuf {
+        let n = TEST_COUNTER.fetch_add(1, Ordering::Relaxed);
+        std::env::temp_dir().join(format!("rust_me_metrics_test_{}_{}.queue", std::process::id(), n))
+    }
+
+    /// `enqueue`/`dequeue_batch`/`enqueue_batch` must report through the
+    /// same `Metrics` sink `Queue::open_with_metrics` was given, with the
+    /// documented counters (`orders.dequeued`, `orders.empty_spins`,
+    /// `queue.backpressure_drops`) incremented by the right amounts —
+    /// including a batch call none of which fits.
+    #[test]
+    fn queue_reports_dequeued_empty_spins_and_backpressure_drops() {
+        let path = temp_queue_path();
+        {
+            // `create_spsc_for_test` lays out the file `Queue::open` (and so
+            // `open_with_metrics`) requires; drop it before reopening so
+            // only one mmap of the file is live at a time.
+            create_spsc_for_test(&path, QUEUE_CAPACITY);
+        }
+
+        // `DepthProbe`'s background thread only checks its stop flag after
+        // waking from `interval`'s sleep, so dropping `queue` at the end of
+        // this test would otherwise block for the full interval — keep it
+        // short, the way the `queue` module's own metrics-enabled tests do.
+        let spy = Arc::new(RecordingMetrics::default());
+        let mut queue = Queue::open_with_metrics(
+            path.to_str().unwrap(),
+            spy.clone(),
+            Duration::from_micros(1),
+        )
+        .unwrap();
+
+        let fill: Vec<Order> = (0..QUEUE_CAPACITY)
+            .map(|i| Order::new(i + 1, 0, *b"TESTSYMB", 1, 100, 0))
+            .collect();
+        assert_eq!(queue.enqueue_batch(&fill).unwrap(), QUEUE_CAPACITY as usize);
+
+        // The ring is now full: a 5-order batch is entirely dropped, and a
+        // single `enqueue` on top of that is dropped too.
+        let overflow: Vec<Order> = (0..5)
+            .map(|i| Order::new(100_000 + i, 0, *b"TESTSYMB", 1, 100, 0))
+            .collect();
+        assert_eq!(queue.enqueue_batch(&overflow).unwrap(), 0);
+        assert!(queue
+            .enqueue(Order::new(999_999, 0, *b"TESTSYMB", 1, 100, 0))
+            .is_err());
+        assert_eq!(spy.total("queue.backpressure_drops"), 6);
+
+        let mut drained = vec![Order::new(0, 0, [0; 8], 0, 0, 0); QUEUE_CAPACITY as usize];
+        assert_eq!(
+            queue.dequeue_batch(&mut drained).unwrap(),
+            QUEUE_CAPACITY as usize
+        );
+        assert_eq!(spy.total("orders.dequeued"), QUEUE_CAPACITY);
+
+        // The ring is empty again, so a few spins should come back empty.
+        assert!(queue.dequeue_spin(5).unwrap().is_none());
+        assert_eq!(spy.total("orders.empty_spins"), 5);
+
+        let _ = std::fs::remove_file(&path);
+    }
+
+    /// `StatsdSink` batches lines until `flush` is called (or the buffer
+    /// fills), then sends them all as one UDP datagram.
+    #[test]
+    fn statsd_sink_buffers_until_flushed_then_sends_one_datagram() {
+        let collector = UdpSocket::bind("127.0.0.1:0").unwrap();
+        collector
+            .set_read_timeout(Some(Duration::from_secs(5)))
+            .unwrap();
+        let target = collector.local_addr().unwrap();
+
+        // A flush interval far longer than the test takes, so only the
+        // explicit `flush()` call below can trigger a send.
+        let sink = StatsdSink::new(&target.to_string(), Duration::from_secs(3600)).unwrap();
+        sink.counter("orders.dequeued", 3);
+        sink.gauge("queue.depth", 42);
+        sink.flush();
+
+        let mut buf = [0u8; 256];
+        let len = collector.recv(&mut buf).unwrap();
+        let payload = std::str::from_utf8(&buf[..len]).unwrap();
+        assert_eq!(payload, "orders.dequeued:3|c\nqueue.depth:42|g");
+    }
+
+    /// Once `MAX_BUFFERED_METRICS` lines accumulate, `push` flushes on its
+    /// own rather than waiting for the interval or an explicit `flush()`.
+    #[test]
+    fn statsd_sink_flushes_automatically_once_the_buffer_fills() {
+        let collector = UdpSocket::bind("127.0.0.1:0").unwrap();
+        collector
+            .set_read_timeout(Some(Duration::from_secs(5)))
+            .unwrap();
+        let target = collector.local_addr().unwrap();
+
+        let sink = StatsdSink::new(&target.to_string(), Duration::from_secs(3600)).unwrap();
+        for i in 0..MAX_BUFFERED_METRICS {
+            sink.counter("orders.dequeued", i as u64);
+        }
+
+        let mut buf = [0u8; 16384];
+        let len = collector.recv(&mut buf).unwrap();
+        let payload = std::str::from_utf8(&buf[..len]).unwrap();
+        assert_eq!(payload.lines().count(), MAX_BUFFERED_METRICS);
+    }
+}
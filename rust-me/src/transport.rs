@@ -0,0 +1,50 @@
+//! Abstracts order queue operations behind a trait, so code that only needs
+//! to enqueue/dequeue orders doesn't have to hard-code the shared-memory
+//! [`Queue`](crate::queue::Queue) — [`MemoryQueue`](crate::memory::MemoryQueue)
+//! implements the same trait entirely in-process, for tests and tooling
+//! that shouldn't need a running Go OMS or a real `/tmp/sex` file.
+
+use crate::queue::{Order, Queue, QueueError};
+
+/// Common order-queue operations, implemented by both the shared-memory
+/// [`Queue`](crate::queue::Queue) and the in-process
+/// [`MemoryQueue`](crate::memory::MemoryQueue).
+///
+/// `enqueue`/`dequeue`/`dequeue_spin` take `&mut self` rather than `&self`,
+/// matching `Queue`'s own SPSC methods: a transport is owned exclusively by
+/// whichever side (producer or consumer) is driving it, the same single-
+/// owner discipline the shared-memory ring already relies on.
+pub trait OrderTransport {
+    /// Enqueue an order, or `QueueError::QueueFull` if there's no room.
+    fn enqueue(&mut self, order: Order) -> Result<(), QueueError>;
+    /// Dequeue the next order, or `Ok(None)` if the queue is empty.
+    fn dequeue(&mut self) -> Result<Option<Order>, QueueError>;
+    /// Like `dequeue`, but busy-spin up to `spins` times before giving up.
+    fn dequeue_spin(&mut self, spins: u32) -> Result<Option<Order>, QueueError>;
+    /// Number of orders currently queued, awaiting consumption.
+    fn depth(&self) -> u64;
+    /// Maximum number of orders the queue can hold.
+    fn capacity(&self) -> u64;
+}
+
+impl OrderTransport for Queue {
+    fn enqueue(&mut self, order: Order) -> Result<(), QueueError> {
+        Queue::enqueue(self, order)
+    }
+
+    fn dequeue(&mut self) -> Result<Option<Order>, QueueError> {
+        Queue::dequeue(self)
+    }
+
+    fn dequeue_spin(&mut self, spins: u32) -> Result<Option<Order>, QueueError> {
+        Queue::dequeue_spin(self, spins)
+    }
+
+    fn depth(&self) -> u64 {
+        Queue::depth(self)
+    }
+
+    fn capacity(&self) -> u64 {
+        Queue::capacity(self)
+    }
+}